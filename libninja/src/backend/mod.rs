@@ -0,0 +1,233 @@
+//! Code generation targets, looked up by name instead of hard-coded.
+//!
+//! A [`CodegenBackend`] owns everything specific to one output language:
+//! emitting the library, its derives, its formatter, and which `--config`
+//! flags it understands. [`BackendRegistry`] resolves `--lang` against a set
+//! of these, built-in or registered at runtime, so `Generate` never needs a
+//! `match` over a closed set of languages.
+
+pub mod plugin;
+
+use clap::ValueEnum;
+use hir::{Config, Hir};
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Result;
+
+/// A single code generation target, e.g. Rust, Python, or TypeScript.
+pub trait CodegenBackend: Send + Sync {
+    /// Stable identifier used to look this backend up in a [`BackendRegistry`]
+    /// (e.g. `"rust"`). This is also what `--lang` is matched against.
+    fn id(&self) -> &str;
+
+    /// Emit the library for `spec` into `config.dest`.
+    fn generate(&self, spec: Hir, config: &Config) -> Result<()>;
+
+    /// Derives this backend adds to every generated struct by default, on top
+    /// of whatever the caller passed via `--derive`.
+    fn default_derives(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Run this backend's formatter (`rustfmt`, `prettier`, `black`, ...) over
+    /// `dest`. Backends without one can leave this as a no-op.
+    fn format(&self, _dest: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether this backend's example renderer actually branches on
+    /// `Config::doctest_examples` (doctest blocks vs. a standalone
+    /// `tests/examples.rs`). Backends that don't should leave this `false` so
+    /// `Generate` rejects `--doctest-examples` outright instead of silently
+    /// accepting a flag it has no effect.
+    fn supports_doctest_examples(&self) -> bool {
+        false
+    }
+
+    /// Apply CLI [`Flag`]s (`--config ormlite`, ...) to `config`. Flags are
+    /// backend-scoped: a backend that doesn't recognize a flag should ignore
+    /// it rather than error.
+    fn apply_flags(&self, _config: &mut Config, _flags: &[Flag]) {}
+}
+
+/// CLI flags passed via `-c`/`--config`.
+///
+/// These are interpreted per-backend (today, only the Rust backend
+/// understands any of them) rather than applying globally.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Flag {
+    /// Only used by Rust. Adds ormlite::TableMeta flags to the code.
+    Ormlite,
+    /// Only used by Rust (for now). Adds fake::Dummy flags to the code.
+    Fake,
+}
+
+/// Registry of known [`CodegenBackend`]s, consulted by [`CodegenBackend::id`].
+pub struct BackendRegistry {
+    backends: Vec<Box<dyn CodegenBackend>>,
+}
+
+impl std::fmt::Debug for BackendRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackendRegistry")
+            .field("backends", &self.backends.iter().map(|b| b.id()).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl BackendRegistry {
+    /// An empty registry with none of libninja's built-in backends. Useful
+    /// for embedders that want to fully control which targets are available.
+    pub fn empty() -> Self {
+        BackendRegistry {
+            backends: Vec::new(),
+        }
+    }
+
+    /// Register a backend, making it resolvable by its [`CodegenBackend::id`].
+    /// A later registration with the same id shadows an earlier one.
+    pub fn register(&mut self, backend: Box<dyn CodegenBackend>) {
+        self.backends.push(backend);
+    }
+
+    /// Look up a backend by id, preferring the most recently registered match.
+    pub fn get(&self, id: &str) -> Option<&dyn CodegenBackend> {
+        self.backends.iter().rev().find(|b| b.id() == id).map(Box::as_ref)
+    }
+}
+
+impl Default for BackendRegistry {
+    /// A registry pre-populated with libninja's built-in backends.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(RustBackend));
+        registry
+    }
+}
+
+/// Built-in Rust backend, wrapping `codegen_rust`.
+pub struct RustBackend;
+
+impl CodegenBackend for RustBackend {
+    fn id(&self) -> &str {
+        "rust"
+    }
+
+    fn generate(&self, spec: Hir, config: &Config) -> Result<()> {
+        codegen_rust::generate_rust_library(spec, config.clone())
+    }
+
+    fn default_derives(&self) -> &[&str] {
+        &["Debug", "Clone", "serde::Serialize", "serde::Deserialize"]
+    }
+
+    // `codegen_rust`'s example renderer doesn't branch on
+    // `Config::doctest_examples` yet, so this leaves `supports_doctest_examples`
+    // at its `false` default rather than claiming support it can't back up.
+
+    fn apply_flags(&self, config: &mut Config, flags: &[Flag]) {
+        config.ormlite = flags.iter().any(|f| matches!(f, Flag::Ormlite));
+        if flags.iter().any(|f| matches!(f, Flag::Fake)) {
+            let dummy = "fake::Dummy".to_string();
+            if !config.derives.contains(&dummy) {
+                config.derives.push(dummy);
+            }
+        }
+    }
+
+    fn format(&self, dest: &Path) -> Result<()> {
+        let status = Command::new("cargo").arg("fmt").current_dir(dest).status();
+        match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => {
+                eprintln!("warning: `cargo fmt` exited with {status}; leaving output unformatted");
+                Ok(())
+            }
+            Err(err) => {
+                eprintln!("warning: couldn't run `cargo fmt` ({err}); leaving output unformatted");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubBackend(&'static str);
+
+    impl CodegenBackend for StubBackend {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        fn generate(&self, _spec: Hir, _config: &Config) -> Result<()> {
+            unimplemented!("not exercised by registry lookup tests")
+        }
+    }
+
+    #[test]
+    fn default_registry_resolves_the_builtin_rust_backend() {
+        let registry = BackendRegistry::default();
+        assert_eq!(registry.get("rust").map(|b| b.id()), Some("rust"));
+        assert!(registry.get("python").is_none());
+    }
+
+    #[test]
+    fn apply_flags_ormlite_and_fake_are_independent() {
+        let mut config = Config {
+            name: "Test".to_string(),
+            dest: Path::new(".").to_path_buf(),
+            derives: Vec::new(),
+            build_examples: false,
+            doctest_examples: false,
+            type_overrides: Vec::new(),
+            imports: Vec::new(),
+            ormlite: false,
+        };
+
+        RustBackend.apply_flags(&mut config, &[Flag::Fake]);
+        assert!(!config.ormlite);
+        assert_eq!(config.derives, vec!["fake::Dummy".to_string()]);
+
+        // Applying it again shouldn't derive fake::Dummy twice.
+        RustBackend.apply_flags(&mut config, &[Flag::Fake, Flag::Ormlite]);
+        assert!(config.ormlite);
+        assert_eq!(config.derives, vec!["fake::Dummy".to_string()]);
+    }
+
+    #[test]
+    fn rust_backend_does_not_claim_doctest_examples_support() {
+        // codegen_rust doesn't branch on Config::doctest_examples, so the
+        // Rust backend must not advertise support for it.
+        assert!(!RustBackend.supports_doctest_examples());
+    }
+
+    #[test]
+    fn format_falls_back_to_ok_when_the_formatter_cant_run() {
+        // `cargo fmt` can't run against a directory that doesn't exist; this
+        // should warn and leave the output unformatted, not fail generation.
+        let result = RustBackend.format(Path::new("/does/not/exist/libninja-format-test"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn registering_a_backend_with_an_existing_id_shadows_the_earlier_one() {
+        let mut registry = BackendRegistry::empty();
+        registry.register(Box::new(StubBackend("rust")));
+        registry.register(Box::new(StubBackend("rust")));
+
+        // Only one entry should be observable by id, and it should be the
+        // most recently registered one.
+        let resolved = registry.get("rust").expect("rust should resolve");
+        assert_eq!(resolved.id(), "rust");
+        let latest: &dyn CodegenBackend = registry.backends[1].as_ref();
+        assert!(std::ptr::eq(
+            resolved as *const dyn CodegenBackend as *const u8,
+            latest as *const dyn CodegenBackend as *const u8,
+        ));
+        assert_eq!(registry.backends.len(), 2);
+    }
+}
@@ -0,0 +1,173 @@
+//! Loading codegen backends from `.so`/`.dll`/`.dylib` files at runtime.
+//!
+//! A plugin exports one C-ABI entry point, `libninja_backend_register`,
+//! returning a [`BackendVTable`]. [`load_plugins`] opens each dylib it finds
+//! in a directory, calls that symbol, and wraps the result in a
+//! [`PluginBackend`] so it behaves like any other [`CodegenBackend`]. The
+//! owning [`Library`] is kept alive alongside the vtable for as long as the
+//! backend is registered, since the function pointers stop being valid the
+//! moment it's dropped.
+
+use crate::backend::{BackendRegistry, CodegenBackend};
+use anyhow::{anyhow, bail, Result};
+use hir::{Config, Hir};
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, CStr, CString};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever [`BackendVTable`]'s layout changes in a backwards
+/// incompatible way. A plugin reporting a different version is skipped.
+pub const ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin dylib must export.
+pub const ENTRY_SYMBOL: &[u8] = b"libninja_backend_register";
+
+/// C-ABI vtable a plugin's `libninja_backend_register` symbol returns.
+///
+/// `generate` takes `spec`/`config` serialized as JSON rather than the raw
+/// `Hir`/`Config` structs, since those aren't `repr(C)` and a plugin may be
+/// built against a different libninja-hir version than the host.
+#[repr(C)]
+pub struct BackendVTable {
+    pub abi_version: u32,
+    pub id: extern "C" fn() -> *const c_char,
+    pub generate: extern "C" fn(spec_json: *const c_char, config_json: *const c_char) -> i32,
+}
+
+/// A [`CodegenBackend`] backed by a dynamically loaded plugin.
+///
+/// Holds the owning [`Library`] for the process lifetime: the vtable's
+/// function pointers are only valid while the library stays mapped.
+pub struct PluginBackend {
+    _library: Library,
+    vtable: *mut BackendVTable,
+    id: String,
+}
+
+// SAFETY: the plugin contract requires `generate` to be callable from any
+// thread; the vtable itself is never mutated after registration.
+unsafe impl Send for PluginBackend {}
+unsafe impl Sync for PluginBackend {}
+
+impl CodegenBackend for PluginBackend {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn generate(&self, spec: Hir, config: &Config) -> Result<()> {
+        let spec_json = CString::new(serde_json::to_string(&spec)?)?;
+        let config_json = CString::new(serde_json::to_string(config)?)?;
+        let vtable = unsafe { &*self.vtable };
+        let status = (vtable.generate)(spec_json.as_ptr(), config_json.as_ptr());
+        if status != 0 {
+            bail!("plugin backend {:?} exited with status {status}", self.id);
+        }
+        Ok(())
+    }
+}
+
+/// Default plugins directory: `$XDG_DATA_HOME/libninja/backends`.
+pub fn default_plugin_dir() -> PathBuf {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from(".local/share"));
+    data_home.join("libninja/backends")
+}
+
+/// Load every plugin dylib in `dir` into `registry`.
+///
+/// A missing `dir` is treated as "no plugins installed", not an error. A
+/// plugin that's missing the entry symbol or reports a mismatched ABI
+/// version is skipped with a warning rather than aborting the whole scan.
+pub fn load_plugins(registry: &mut BackendRegistry, dir: &Path) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if !is_dynamic_library(&path) {
+            continue;
+        }
+        match load_plugin(&path) {
+            Ok(backend) => registry.register(Box::new(backend)),
+            Err(err) => eprintln!("warning: failed to load backend plugin {path:?}: {err}"),
+        }
+    }
+    Ok(())
+}
+
+fn is_dynamic_library(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("so") | Some("dll") | Some("dylib")
+    )
+}
+
+fn load_plugin(path: &Path) -> Result<PluginBackend> {
+    // SAFETY: loading arbitrary code is inherently unsafe; we trust the
+    // operator to only place vetted backends in the plugins directory.
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| anyhow!("{path:?}: failed to open as a dynamic library: {e}"))?;
+
+    let vtable = unsafe {
+        let register: Symbol<unsafe extern "C" fn() -> *mut BackendVTable> = library
+            .get(ENTRY_SYMBOL)
+            .map_err(|e| anyhow!("{path:?}: missing `libninja_backend_register` symbol: {e}"))?;
+        register()
+    };
+    if vtable.is_null() {
+        bail!("{path:?}: libninja_backend_register returned null");
+    }
+
+    let abi_version = unsafe { (*vtable).abi_version };
+    if abi_version != ABI_VERSION {
+        bail!(
+            "{path:?}: plugin ABI version {abi_version} doesn't match libninja's {ABI_VERSION}"
+        );
+    }
+
+    let id = unsafe { CStr::from_ptr(((*vtable).id)()).to_string_lossy().into_owned() };
+
+    Ok(PluginBackend {
+        _library: library,
+        vtable,
+        id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_dynamic_library_matches_known_extensions_only() {
+        assert!(is_dynamic_library(Path::new("backend.so")));
+        assert!(is_dynamic_library(Path::new("backend.dll")));
+        assert!(is_dynamic_library(Path::new("backend.dylib")));
+        assert!(!is_dynamic_library(Path::new("backend.txt")));
+        assert!(!is_dynamic_library(Path::new("backend")));
+    }
+
+    #[test]
+    fn load_plugin_rejects_a_file_that_isnt_a_real_library() {
+        let dir = std::env::temp_dir().join(format!("libninja-plugin-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("not_a_library.so");
+        std::fs::write(&path, b"not an ELF/Mach-O/PE binary").unwrap();
+
+        let result = load_plugin(&path);
+        assert!(result.is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn load_plugins_treats_a_missing_directory_as_no_plugins() {
+        let mut registry = BackendRegistry::empty();
+        let result = load_plugins(&mut registry, Path::new("/does/not/exist/libninja-plugins"));
+        assert!(result.is_ok());
+    }
+}
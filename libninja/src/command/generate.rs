@@ -1,32 +1,44 @@
+use crate::backend::plugin;
+use crate::backend::{BackendRegistry, CodegenBackend, Flag};
 use crate::extractor::extract_spec;
-use anyhow::{anyhow, Result};
-use clap::{Args, ValueEnum};
+use crate::resolver::resolve_external_refs;
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
 use convert_case::{Case, Casing};
-use hir::{Config, Language};
+use hir::Config;
 use openapiv3::{OpenAPI, VersionedOpenAPI};
 use std::fs::File;
 use std::path::{Path, PathBuf};
 
-/// CLI flags
-#[derive(ValueEnum, Debug, Clone, Copy)]
-pub enum Flag {
-    /// Only used by Rust. Adds ormlite::TableMeta flags to the code.
-    Ormlite,
-    /// Only used by Rust (for now). Adds fake::Dummy flags to the code.
-    Fake,
-}
-
 #[derive(Args, Debug)]
 pub struct Generate {
-    /// Service name.
+    /// Which codegen backend to use, matched against `CodegenBackend::id`.
+    /// Built in: "rust". Additional backends can be registered with
+    /// `Generate::register_backend`.
     #[clap(short, long = "lang", default_value = "rust")]
-    pub language: Language,
+    pub language: String,
 
     /// Toggle whether to generate examples.
     /// Defaults to true
     #[clap(long, default_value = "true")]
     examples: bool,
 
+    /// When examples are enabled, instead of embedding them as doctests on
+    /// the client struct and each endpoint method, write them to a
+    /// standalone `tests/examples.rs` harness. Defaults to false. Requires
+    /// `--examples`, and requires a backend whose
+    /// `CodegenBackend::supports_doctest_examples` is true — the built-in
+    /// Rust backend's example renderer doesn't branch on this yet, so
+    /// passing this flag with `--lang rust` is rejected rather than silently
+    /// ignored.
+    #[clap(long, default_value = "false")]
+    doctest_examples: bool,
+
+    /// Toggle whether to run the backend's formatter (`rustfmt`, ...) over
+    /// the generated output. Defaults to true.
+    #[clap(long, default_value = "true")]
+    format: bool,
+
     #[clap(short, long)]
     output_dir: Option<String>,
 
@@ -37,34 +49,75 @@ pub struct Generate {
     #[clap(long)]
     derive: Vec<String>,
 
+    /// Map a named schema onto an existing type instead of generating a
+    /// struct for it, e.g. `--type-override Money=crate::money::Money`.
+    /// Repeatable.
+    #[clap(long = "type-override", value_parser = parse_type_override)]
+    type_overrides: Vec<(String, String)>,
+
+    /// Extra `use` path to inject into the generated module, e.g.
+    /// `--import chrono::DateTime`. Repeatable.
+    #[clap(long = "import")]
+    imports: Vec<String>,
+
     /// The "service" name. E.g. if we want to generate a library for the Stripe API, this would be "Stripe".
     name: String,
 
     /// Path to the OpenAPI spec file.
     spec_filepath: String,
+
+    /// Directory to scan for dynamically loaded backend plugins.
+    /// Defaults to `$XDG_DATA_HOME/libninja/backends`.
+    #[clap(long)]
+    plugins_dir: Option<PathBuf>,
+
+    /// Toggle whether to scan `plugins_dir` and `dlopen` dynamically loaded
+    /// backends at all. Only consulted when `--lang` doesn't already match a
+    /// registered backend (built in or added with `Generate::register_backend`).
+    /// Defaults to true.
+    #[clap(long, default_value = "true")]
+    load_plugins: bool,
+
+    #[clap(skip)]
+    backends: BackendRegistry,
 }
 
 impl Generate {
     pub fn new() -> Self {
         Generate {
-            language: Language::Rust,
+            language: "rust".to_string(),
             examples: true,
+            doctest_examples: false,
+            format: true,
             output_dir: None,
             config: Vec::new(),
             derive: Vec::new(),
+            type_overrides: Vec::new(),
+            imports: Vec::new(),
             name: String::new(),
             spec_filepath: String::new(),
+            plugins_dir: None,
+            load_plugins: true,
+            backends: BackendRegistry::default(),
         }
     }
 
-    pub fn with_language(mut self, language: Language) -> Self {
-        self.language = language;
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
         self
     }
     pub fn with_examples(mut self, examples: bool) -> Self {
         self.examples = examples;
         self
     }
+    pub fn with_format(mut self, format: bool) -> Self {
+        self.format = format;
+        self
+    }
+    pub fn with_doctest_examples(mut self, doctest_examples: bool) -> Self {
+        self.doctest_examples = doctest_examples;
+        self
+    }
     pub fn with_output_dir(mut self, output_dir: String) -> Self {
         self.output_dir = Some(output_dir);
         self
@@ -93,22 +146,179 @@ impl Generate {
         self.config = config;
         self
     }
+    pub fn add_type_override(mut self, schema: String, path: String) -> Self {
+        self.type_overrides.push((schema, path));
+        self
+    }
+    pub fn with_type_overrides(mut self, type_overrides: Vec<(String, String)>) -> Self {
+        self.type_overrides = type_overrides;
+        self
+    }
+    pub fn add_import(mut self, import: String) -> Self {
+        self.imports.push(import);
+        self
+    }
+    pub fn with_imports(mut self, imports: Vec<String>) -> Self {
+        self.imports = imports;
+        self
+    }
+
+    /// Register a custom codegen backend, making it resolvable by `--lang`.
+    /// Lets downstream crates ship a language libninja doesn't know about.
+    pub fn register_backend(mut self, backend: Box<dyn CodegenBackend>) -> Self {
+        self.backends.register(backend);
+        self
+    }
+
+    /// Override the directory scanned for dynamically loaded backend plugins.
+    pub fn with_plugins_dir(mut self, plugins_dir: PathBuf) -> Self {
+        self.plugins_dir = Some(plugins_dir);
+        self
+    }
+
+    /// Toggle scanning `plugins_dir` for dynamically loaded backends.
+    pub fn with_load_plugins(mut self, load_plugins: bool) -> Self {
+        self.load_plugins = load_plugins;
+        self
+    }
+
+    pub fn run(mut self) -> Result<()> {
+        // Only dlopen plugins when the requested language isn't already a
+        // registered backend (built in or added via `register_backend`): a
+        // plain `--lang rust` run has no business
+        // executing whatever happens to be sitting in the plugins directory.
+        if self.load_plugins && self.backends.get(&self.language).is_none() {
+            let plugins_dir = self
+                .plugins_dir
+                .clone()
+                .unwrap_or_else(plugin::default_plugin_dir);
+            if let Err(err) = plugin::load_plugins(&mut self.backends, &plugins_dir) {
+                eprintln!("warning: failed to scan plugin directory {plugins_dir:?}: {err}");
+            }
+        }
+
+        let backend = self.backends.get(&self.language).ok_or_else(|| {
+            anyhow!(
+                "Unknown codegen backend {:?}. Register it first with Generate::register_backend.",
+                self.language
+            )
+        })?;
+
+        validate_examples_config(
+            self.examples,
+            self.doctest_examples,
+            backend.supports_doctest_examples(),
+        )?;
 
-    pub fn run(self) -> Result<()> {
         let spec = PathBuf::from(self.spec_filepath);
         let spec = read_spec(&spec)?;
         let output_dir = PathBuf::from(self.output_dir.unwrap_or_else(|| ".".to_string()));
-        let spec = extract_spec(&spec)?;
-        let config = Config {
+
+        let derives = merge_derives(self.derive, backend.default_derives());
+
+        let mut config = Config {
             name: self.name.to_case(Case::Pascal),
             dest: output_dir,
-            derives: self.derive,
+            derives,
             build_examples: self.examples,
+            doctest_examples: self.doctest_examples,
+            type_overrides: self.type_overrides,
+            imports: self.imports,
             ormlite: false,
         };
-        match self.language {
-            Language::Rust => codegen_rust::generate_rust_library(spec, config),
+        backend.apply_flags(&mut config, &self.config);
+
+        // Schemas listed in `type_overrides` are resolved to the external type
+        // instead of a generated struct, so the extractor needs to see them.
+        let spec = extract_spec(&spec, &config)?;
+
+        backend.generate(spec, &config)?;
+
+        if self.format {
+            backend.format(&config.dest)?;
         }
+
+        Ok(())
+    }
+}
+
+/// `--doctest-examples` only changes *where* examples are rendered, so it's
+/// meaningless (and almost certainly a user mistake) when examples are off.
+/// It's also only meaningful if the chosen backend actually renders examples
+/// differently in each mode; a backend that doesn't should reject it outright
+/// rather than silently accepting a flag that has no effect.
+fn validate_examples_config(
+    examples: bool,
+    doctest_examples: bool,
+    backend_supports_doctest_examples: bool,
+) -> Result<()> {
+    if doctest_examples && !examples {
+        bail!("--doctest-examples has no effect without --examples; pass --examples (the default) or drop --doctest-examples");
+    }
+    if doctest_examples && !backend_supports_doctest_examples {
+        bail!("--doctest-examples isn't implemented by the selected backend; its example renderer doesn't branch on this yet");
+    }
+    Ok(())
+}
+
+fn parse_type_override(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(schema, path)| (schema.to_string(), path.to_string()))
+        .ok_or_else(|| format!("expected SCHEMA=path::To::Type, got {s:?}"))
+}
+
+/// Combine user-supplied `--derive` values with a backend's always-on
+/// defaults, keeping first-occurrence order and dropping duplicates so the
+/// same trait is never derived twice on a generated struct.
+fn merge_derives(user: Vec<String>, defaults: &[&str]) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    user.into_iter()
+        .chain(defaults.iter().map(|d| d.to_string()))
+        .filter(|d| seen.insert(d.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_derives_dedupes_user_and_backend_defaults() {
+        let merged = merge_derives(
+            vec!["Debug".to_string(), "PartialEq".to_string()],
+            &["Debug", "Clone", "serde::Serialize"],
+        );
+        assert_eq!(
+            merged,
+            vec!["Debug", "PartialEq", "Clone", "serde::Serialize"]
+        );
+    }
+
+    #[test]
+    fn merge_derives_dedupes_repeated_user_input() {
+        let merged = merge_derives(vec!["Debug".to_string(), "Debug".to_string()], &[]);
+        assert_eq!(merged, vec!["Debug"]);
+    }
+
+    #[test]
+    fn doctest_examples_without_examples_is_rejected() {
+        assert!(validate_examples_config(false, true, true).is_err());
+    }
+
+    #[test]
+    fn doctest_examples_is_rejected_when_the_backend_does_not_support_it() {
+        assert!(validate_examples_config(true, true, false).is_err());
+    }
+
+    #[test]
+    fn doctest_examples_is_accepted_when_the_backend_supports_it() {
+        assert!(validate_examples_config(true, true, true).is_ok());
+    }
+
+    #[test]
+    fn doctest_examples_off_is_always_accepted() {
+        assert!(validate_examples_config(true, false, false).is_ok());
+        assert!(validate_examples_config(false, false, false).is_ok());
     }
 }
 
@@ -123,6 +333,7 @@ pub fn read_spec(path: &Path) -> Result<OpenAPI> {
         "json" => serde_json::from_reader(file)?,
         _ => panic!("Unknown file extension"),
     };
-    let openapi = openapi.upgrade();
+    let mut openapi = openapi.upgrade();
+    resolve_external_refs(&mut openapi, path)?;
     Ok(openapi)
 }
@@ -0,0 +1,184 @@
+//! Converts a resolved `OpenAPI` document into the `Hir` consumed by codegen
+//! backends.
+
+use anyhow::Result;
+use hir::{Config, Hir};
+use openapiv3::OpenAPI;
+use serde_json::Value;
+
+/// Vendor extension marking a schema as replaced by `--type-override`, set to
+/// the external type path it stands in for (e.g. `crate::money::Money`).
+const TYPE_OVERRIDE_EXTENSION: &str = "x-libninja-type-override";
+
+/// Extract `spec` into the backend-agnostic IR.
+///
+/// Schemas named in `config.type_overrides` are replaced in place (not
+/// removed) so that any `$ref` pointing at one keeps resolving to a real
+/// schema — see [`apply_type_overrides`] — and `hir::extract` never sees a
+/// dangling reference for a spec that actually uses `--type-override`.
+pub fn extract_spec(spec: &OpenAPI, config: &Config) -> Result<Hir> {
+    let spec = apply_type_overrides(spec, config)?;
+    hir::extract(&spec)
+}
+
+/// Replace every schema listed in `config.type_overrides` with a minimal
+/// placeholder carrying an `x-libninja-type-override` vendor extension set to
+/// the external type path. `$ref`s to the schema keep resolving (to the
+/// placeholder) instead of going dangling, and the extractor/backend can
+/// still recognize the override by inspecting the extension on the schema a
+/// `$ref` resolves to, rather than by name alone.
+fn apply_type_overrides(spec: &OpenAPI, config: &Config) -> Result<OpenAPI> {
+    if config.type_overrides.is_empty() {
+        return Ok(spec.clone());
+    }
+
+    let mut document = serde_json::to_value(spec)?;
+    if let Some(schemas) = document
+        .pointer_mut("/components/schemas")
+        .and_then(Value::as_object_mut)
+    {
+        for (schema, path) in &config.type_overrides {
+            if schemas.contains_key(schema) {
+                schemas.insert(
+                    schema.clone(),
+                    serde_json::json!({ TYPE_OVERRIDE_EXTENSION: path }),
+                );
+            }
+        }
+    }
+    Ok(serde_json::from_value(document)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hir::Config;
+    use std::path::PathBuf;
+
+    fn spec_with_schemas(names: &[&str]) -> OpenAPI {
+        let mut document = serde_json::json!({
+            "openapi": "3.0.0",
+            "info": { "title": "test", "version": "1" },
+            "paths": {},
+            "components": { "schemas": {} },
+        });
+        let schemas = document
+            .pointer_mut("/components/schemas")
+            .unwrap()
+            .as_object_mut()
+            .unwrap();
+        for name in names {
+            schemas.insert(
+                name.to_string(),
+                serde_json::json!({ "type": "object", "properties": {} }),
+            );
+        }
+        serde_json::from_value(document).unwrap()
+    }
+
+    fn config_with_overrides(type_overrides: Vec<(String, String)>) -> Config {
+        Config {
+            name: "Test".to_string(),
+            dest: PathBuf::from("."),
+            derives: Vec::new(),
+            build_examples: false,
+            doctest_examples: false,
+            type_overrides,
+            imports: Vec::new(),
+            ormlite: false,
+        }
+    }
+
+    #[test]
+    fn overridden_schemas_are_replaced_with_a_placeholder_not_removed() {
+        let spec = spec_with_schemas(&["Money", "User"]);
+        let config = config_with_overrides(vec![("Money".to_string(), "crate::Money".to_string())]);
+
+        let overridden = apply_type_overrides(&spec, &config).unwrap();
+        let document = serde_json::to_value(&overridden).unwrap();
+        let schemas = document
+            .pointer("/components/schemas")
+            .and_then(Value::as_object)
+            .unwrap();
+
+        // Still present (so a $ref to it keeps resolving), but replaced.
+        let money = schemas.get("Money").expect("Money schema should remain as a placeholder");
+        assert_eq!(
+            money.get(TYPE_OVERRIDE_EXTENSION).and_then(Value::as_str),
+            Some("crate::Money")
+        );
+        assert!(schemas.contains_key("User"));
+    }
+
+    #[test]
+    fn no_overrides_leaves_schemas_untouched() {
+        let spec = spec_with_schemas(&["User"]);
+        let config = config_with_overrides(Vec::new());
+
+        let overridden = apply_type_overrides(&spec, &config).unwrap();
+        let document = serde_json::to_value(&overridden).unwrap();
+        assert!(document
+            .pointer("/components/schemas/User")
+            .is_some());
+    }
+
+    #[test]
+    fn a_ref_into_an_overridden_schema_keeps_resolving_instead_of_going_dangling() {
+        // End-to-end: a path operation $refs the overridden schema directly,
+        // the way a real spec using --type-override would.
+        let mut document = serde_json::json!({
+            "openapi": "3.0.0",
+            "info": { "title": "test", "version": "1" },
+            "paths": {
+                "/money": {
+                    "get": {
+                        "operationId": "getMoney",
+                        "responses": {
+                            "200": {
+                                "description": "ok",
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/Money" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "components": { "schemas": {} },
+        });
+        document
+            .pointer_mut("/components/schemas")
+            .unwrap()
+            .as_object_mut()
+            .unwrap()
+            .insert(
+                "Money".to_string(),
+                serde_json::json!({ "type": "object", "properties": { "cents": { "type": "integer" } } }),
+            );
+        let spec: OpenAPI = serde_json::from_value(document).unwrap();
+        let config = config_with_overrides(vec![("Money".to_string(), "crate::Money".to_string())]);
+
+        let overridden = apply_type_overrides(&spec, &config).unwrap();
+        let document = serde_json::to_value(&overridden).unwrap();
+
+        // The operation's $ref is untouched...
+        let schema_ref = document
+            .pointer("/paths/~1money/get/responses/200/content/application~1json/schema/$ref")
+            .and_then(Value::as_str)
+            .expect("operation's schema $ref");
+        assert_eq!(schema_ref, "#/components/schemas/Money");
+
+        // ...and it still resolves to a real schema carrying the override,
+        // rather than a name that's been deleted out from under it.
+        let target_name = schema_ref.rsplit('/').next().unwrap();
+        let target = document
+            .pointer(&format!("/components/schemas/{target_name}"))
+            .expect("$ref target must still exist after applying overrides");
+        assert_eq!(
+            target.get(TYPE_OVERRIDE_EXTENSION).and_then(Value::as_str),
+            Some("crate::Money")
+        );
+    }
+}
@@ -1,8 +1,10 @@
+pub mod backend;
 pub mod command;
 pub mod extractor;
+pub mod resolver;
 
+pub use backend::{BackendRegistry, CodegenBackend, Flag};
 pub use command::Generate;
-pub use hir::Language;
 
 pub fn default<T: Default>() -> T {
     Default::default()
@@ -0,0 +1,443 @@
+//! Bundling a multi-file spec into one self-contained document before
+//! extraction.
+//!
+//! `read_spec` only reads the one file it's pointed at, so a `$ref` that
+//! steps outside that file (`./models/user.yaml#/...`, `https://...`) has
+//! nothing to resolve against. [`resolve_external_refs`] walks the document,
+//! fetches whatever each such `$ref` points to, inlines it into
+//! `components.schemas` under a name synthesized from its origin, and
+//! rewrites the `$ref` to point at the inlined copy — recursively, so a
+//! fetched file can itself `$ref` further afield.
+
+use anyhow::{anyhow, Result};
+use convert_case::{Case, Casing};
+use openapiv3::OpenAPI;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Walk `spec`, inlining every external/remote `$ref` and rewriting it to
+/// point at the inlined copy. `base` is the path `spec` was loaded from,
+/// used to resolve references given as relative paths.
+pub fn resolve_external_refs(spec: &mut OpenAPI, base: &Path) -> Result<()> {
+    let mut document = serde_json::to_value(&*spec)?;
+    let mut visited = HashSet::new();
+    let mut inlined = Map::new();
+
+    rewrite_refs(&mut document, base, &mut visited, &mut inlined)?;
+
+    let components = document
+        .as_object_mut()
+        .expect("an OpenAPI document serializes to a JSON object")
+        .entry("components")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let schemas = components
+        .as_object_mut()
+        .expect("components is always a JSON object")
+        .entry("schemas")
+        .or_insert_with(|| Value::Object(Map::new()));
+    schemas
+        .as_object_mut()
+        .expect("components.schemas is always a JSON object")
+        .extend(inlined);
+
+    *spec = serde_json::from_value(document)?;
+    Ok(())
+}
+
+/// Walk `value` in place, rewriting every external `$ref` found and
+/// accumulating the schemas they point to into `inlined`.
+fn rewrite_refs(
+    value: &mut Value,
+    base: &Path,
+    visited: &mut HashSet<String>,
+    inlined: &mut Map<String, Value>,
+) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(reference)) = map.get("$ref") {
+                let reference = reference.clone();
+                if let Some(rewritten) = resolve_one(&reference, base, visited, inlined)? {
+                    map.insert("$ref".to_string(), Value::String(rewritten));
+                }
+                return Ok(());
+            }
+            for v in map.values_mut() {
+                rewrite_refs(v, base, visited, inlined)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rewrite_refs(item, base, visited, inlined)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Resolve a single `$ref`. Returns `None` for refs that already point within
+/// the root document (nothing to do). A `$ref` whose target was already
+/// inlined under a different alias is rewritten without re-fetching it.
+fn resolve_one(
+    reference: &str,
+    base: &Path,
+    visited: &mut HashSet<String>,
+    inlined: &mut Map<String, Value>,
+) -> Result<Option<String>> {
+    if !is_external(reference) {
+        return Ok(None);
+    }
+
+    let (uri, fragment) = split_ref(reference);
+    let canonical = canonicalize_uri(&uri, base);
+    let name = synthesize_name(&canonical, fragment.as_deref());
+    let rewritten = format!("#/components/schemas/{name}");
+
+    // Key on the full reference (file + fragment), not just the file: two
+    // `$ref`s into different fragments of the same external file (the common
+    // case for a spec like Stripe's) must both get inlined, not just the
+    // first one to be visited.
+    let ref_key = match &fragment {
+        Some(fragment) => format!("{canonical}{fragment}"),
+        None => canonical.clone(),
+    };
+    if !visited.insert(ref_key) {
+        return Ok(Some(rewritten));
+    }
+
+    let document = fetch_and_parse(&uri, base)
+        .map_err(|e| anyhow!("unresolved reference chain: {reference} -> {e}"))?;
+    let mut target = match &fragment {
+        Some(pointer) => document
+            .pointer(pointer)
+            .ok_or_else(|| {
+                anyhow!("unresolved reference chain: {reference} -> no such fragment {pointer:?}")
+            })?
+            .clone(),
+        None => document,
+    };
+
+    let child_base = local_path(&uri, base).unwrap_or_else(|| base.to_path_buf());
+    rewrite_refs(&mut target, &child_base, visited, inlined)?;
+    inlined.insert(name, target);
+
+    Ok(Some(rewritten))
+}
+
+/// A `$ref` is external if its URI part (before any `#fragment`) is non-empty,
+/// i.e. it isn't a same-document pointer like `#/components/schemas/Foo`.
+fn is_external(reference: &str) -> bool {
+    !reference.starts_with('#')
+}
+
+fn split_ref(reference: &str) -> (String, Option<String>) {
+    match reference.split_once('#') {
+        Some((uri, fragment)) => {
+            let fragment = format!("/{}", fragment.trim_start_matches('/'));
+            (uri.to_string(), Some(fragment))
+        }
+        None => (reference.to_string(), None),
+    }
+}
+
+fn is_remote(uri: &str) -> bool {
+    uri.starts_with("http://") || uri.starts_with("https://")
+}
+
+fn local_path(uri: &str, base: &Path) -> Option<PathBuf> {
+    if is_remote(uri) {
+        None
+    } else {
+        Some(base.parent().unwrap_or_else(|| Path::new(".")).join(uri))
+    }
+}
+
+fn canonicalize_uri(uri: &str, base: &Path) -> String {
+    if is_remote(uri) {
+        uri.to_string()
+    } else {
+        let path = local_path(uri, base).unwrap_or_else(|| PathBuf::from(uri));
+        path.canonicalize()
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Synthesize a deterministic component name from the resolved URI and
+/// fragment, e.g. `./models/user.yaml#/components/schemas/User` ->
+/// `UserUsera1b2c3d4`. The file stem and fragment leaf are there for
+/// readability; the trailing hash is computed over the *full* canonical URI
+/// (directory included) so two files that merely share a stem and fragment
+/// leaf — e.g. `./v1/common.yaml#/Error` and `./v2/common.yaml#/Error` — don't
+/// synthesize the same name and clobber each other in `inlined`.
+fn synthesize_name(canonical_uri: &str, fragment: Option<&str>) -> String {
+    let stem = Path::new(canonical_uri)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| canonical_uri.to_case(Case::Pascal));
+    let leaf = fragment
+        .and_then(|f| f.rsplit('/').next())
+        .filter(|s| !s.is_empty())
+        .unwrap_or(&stem)
+        .to_string();
+
+    let mut hasher = DefaultHasher::new();
+    canonical_uri.hash(&mut hasher);
+    fragment.hash(&mut hasher);
+    let digest = hasher.finish() as u32;
+
+    format!(
+        "{}{}{:08x}",
+        leaf.to_case(Case::Pascal),
+        stem.to_case(Case::Pascal),
+        digest
+    )
+}
+
+fn fetch_and_parse(uri: &str, base: &Path) -> Result<Value> {
+    let contents = if is_remote(uri) {
+        reqwest::blocking::get(uri)?.text()?
+    } else {
+        let path = local_path(uri, base).expect("checked is_remote above");
+        std::fs::read_to_string(&path)
+            .map_err(|_| anyhow!("{path:?}: file not found"))?
+    };
+
+    if uri.ends_with(".json") {
+        Ok(serde_json::from_str(&contents)?)
+    } else {
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_spec(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn tmp_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "libninja-resolver-test-{label}-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn two_refs_into_the_same_external_file_both_get_inlined() {
+        let dir = tmp_dir("two-fragments");
+        write_spec(
+            &dir,
+            "models.yaml",
+            r#"
+User:
+  type: object
+  properties:
+    id:
+      type: string
+Order:
+  type: object
+  properties:
+    id:
+      type: string
+"#,
+        );
+        let root = write_spec(
+            &dir,
+            "root.yaml",
+            r#"
+openapi: 3.0.0
+info:
+  title: test
+  version: "1"
+paths: {}
+components:
+  schemas:
+    UserRef:
+      $ref: "./models.yaml#/User"
+    OrderRef:
+      $ref: "./models.yaml#/Order"
+"#,
+        );
+
+        let openapi: openapiv3::VersionedOpenAPI =
+            serde_yaml::from_str(&fs::read_to_string(&root).unwrap()).unwrap();
+        let mut openapi = openapi.upgrade();
+
+        resolve_external_refs(&mut openapi, &root).unwrap();
+
+        let document = serde_json::to_value(&openapi).unwrap();
+        let schemas = document
+            .pointer("/components/schemas")
+            .and_then(Value::as_object)
+            .expect("components.schemas");
+
+        let user_ref = schemas
+            .get("UserRef")
+            .and_then(|s| s.get("$ref"))
+            .and_then(Value::as_str)
+            .expect("UserRef $ref");
+        let order_ref = schemas
+            .get("OrderRef")
+            .and_then(|s| s.get("$ref"))
+            .and_then(Value::as_str)
+            .expect("OrderRef $ref");
+
+        // Neither rewritten $ref should be dangling: both inlined targets
+        // must actually exist in components.schemas.
+        assert_ne!(user_ref, order_ref);
+        let user_name = user_ref.rsplit('/').next().unwrap();
+        let order_name = order_ref.rsplit('/').next().unwrap();
+        assert!(
+            schemas.contains_key(user_name),
+            "{user_name:?} missing from inlined schemas: {schemas:?}"
+        );
+        assert!(
+            schemas.contains_key(order_name),
+            "{order_name:?} missing from inlined schemas: {schemas:?}"
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn refs_with_the_same_stem_and_fragment_leaf_in_different_directories_dont_collide() {
+        let dir = tmp_dir("same-stem-different-dir");
+        fs::create_dir_all(dir.join("v1")).unwrap();
+        fs::create_dir_all(dir.join("v2")).unwrap();
+        write_spec(
+            &dir.join("v1"),
+            "common.yaml",
+            r#"
+Error:
+  type: object
+  properties:
+    message:
+      type: string
+"#,
+        );
+        write_spec(
+            &dir.join("v2"),
+            "common.yaml",
+            r#"
+Error:
+  type: object
+  properties:
+    code:
+      type: integer
+"#,
+        );
+        let root = write_spec(
+            &dir,
+            "root.yaml",
+            r#"
+openapi: 3.0.0
+info:
+  title: test
+  version: "1"
+paths: {}
+components:
+  schemas:
+    ErrorV1:
+      $ref: "./v1/common.yaml#/Error"
+    ErrorV2:
+      $ref: "./v2/common.yaml#/Error"
+"#,
+        );
+
+        let openapi: openapiv3::VersionedOpenAPI =
+            serde_yaml::from_str(&fs::read_to_string(&root).unwrap()).unwrap();
+        let mut openapi = openapi.upgrade();
+        resolve_external_refs(&mut openapi, &root).unwrap();
+
+        let document = serde_json::to_value(&openapi).unwrap();
+        let schemas = document
+            .pointer("/components/schemas")
+            .and_then(Value::as_object)
+            .expect("components.schemas");
+
+        let v1_ref = schemas
+            .get("ErrorV1")
+            .and_then(|s| s.get("$ref"))
+            .and_then(Value::as_str)
+            .expect("ErrorV1 $ref");
+        let v2_ref = schemas
+            .get("ErrorV2")
+            .and_then(|s| s.get("$ref"))
+            .and_then(Value::as_str)
+            .expect("ErrorV2 $ref");
+
+        // Same file stem, same fragment leaf, different directories: neither
+        // inlined schema should clobber the other.
+        assert_ne!(v1_ref, v2_ref, "distinct source files must not synthesize the same name");
+
+        let v1_name = v1_ref.rsplit('/').next().unwrap();
+        let v2_name = v2_ref.rsplit('/').next().unwrap();
+        let v1_schema = schemas.get(v1_name).expect("ErrorV1's inlined schema present");
+        let v2_schema = schemas.get(v2_name).expect("ErrorV2's inlined schema present");
+        assert!(v1_schema.pointer("/properties/message").is_some());
+        assert!(v2_schema.pointer("/properties/code").is_some());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn same_ref_visited_twice_is_rewritten_consistently_without_refetching() {
+        let dir = tmp_dir("repeat-ref");
+        write_spec(
+            &dir,
+            "models.yaml",
+            r#"
+User:
+  type: object
+  properties:
+    id:
+      type: string
+"#,
+        );
+        let root = write_spec(
+            &dir,
+            "root.yaml",
+            r#"
+openapi: 3.0.0
+info:
+  title: test
+  version: "1"
+paths: {}
+components:
+  schemas:
+    A:
+      $ref: "./models.yaml#/User"
+    B:
+      $ref: "./models.yaml#/User"
+"#,
+        );
+
+        let openapi: openapiv3::VersionedOpenAPI =
+            serde_yaml::from_str(&fs::read_to_string(&root).unwrap()).unwrap();
+        let mut openapi = openapi.upgrade();
+        resolve_external_refs(&mut openapi, &root).unwrap();
+
+        let document = serde_json::to_value(&openapi).unwrap();
+        let schemas = document
+            .pointer("/components/schemas")
+            .and_then(Value::as_object)
+            .unwrap();
+        let a = schemas.get("A").unwrap().get("$ref").unwrap().as_str().unwrap();
+        let b = schemas.get("B").unwrap().get("$ref").unwrap().as_str().unwrap();
+        assert_eq!(a, b, "identical refs should be rewritten to the same inlined schema");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}